@@ -0,0 +1,297 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use vm_core::{
+    utils::{ByteReader, ByteWriter, Deserializable, Serializable},
+    EMPTY_WORD,
+};
+use vm_processor::DeserializationError;
+
+use super::{AccountStorage, AccountStorageHeader, StorageSlotType, Word};
+
+// STORAGE HEADER DELTA
+// ================================================================================================
+
+/// A sparse encoding of the differences between two [AccountStorageHeader]s.
+///
+/// A delta records the base header's slot count (so [AccountStorageHeader::apply] can check it is
+/// being applied to the right header), the slot count of the header it was diffed against, and a
+/// sparse list of `(index, new_type, new_value)` records for every index whose `(StorageSlotType,
+/// Word)` pair differs -- including type transitions between [StorageSlotType::Value] and
+/// [StorageSlotType::Map], and any trailing slots that were added. This is typically far smaller
+/// than a full header when only a handful of slots change between two blocks.
+///
+/// Slots removed from the end of the header need no record at all: truncation is implied by a
+/// `new_num_slots` smaller than `base_num_slots`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageHeaderDelta {
+    base_num_slots: u8,
+    new_num_slots: u8,
+    changes: Vec<(u8, StorageSlotType, Word)>,
+}
+
+impl StorageHeaderDelta {
+    /// Returns the slot count of the header this delta must be applied to.
+    pub fn base_num_slots(&self) -> u8 {
+        self.base_num_slots
+    }
+
+    /// Returns the slot count of the header this delta reconstructs.
+    pub fn new_num_slots(&self) -> u8 {
+        self.new_num_slots
+    }
+
+    /// Returns an iterator over the sparse `(index, new_type, new_value)` change records.
+    pub fn changes(&self) -> impl Iterator<Item = &(u8, StorageSlotType, Word)> {
+        self.changes.iter()
+    }
+}
+
+impl AccountStorageHeader {
+    // DELTAS
+    // --------------------------------------------------------------------------------------------
+
+    /// Computes the [StorageHeaderDelta] that turns `self` into `other`.
+    pub fn diff(&self, other: &AccountStorageHeader) -> StorageHeaderDelta {
+        let base_slots: Vec<&(StorageSlotType, Word)> = self.slots().collect();
+        let mut changes = Vec::new();
+
+        for (index, new_slot) in other.slots().enumerate() {
+            let changed = match base_slots.get(index) {
+                Some(old_slot) => *old_slot != new_slot,
+                None => true,
+            };
+            if changed {
+                changes.push((index as u8, new_slot.0, new_slot.1));
+            }
+        }
+
+        StorageHeaderDelta {
+            base_num_slots: self.num_slots() as u8,
+            new_num_slots: other.num_slots() as u8,
+            changes,
+        }
+    }
+
+    /// Applies `delta` to `self`, returning the header it was diffed against.
+    ///
+    /// # Errors
+    /// - Returns [StorageHeaderDeltaError::BaseMismatch] if `delta`'s base slot count does not
+    ///   match `self`'s slot count.
+    /// - Returns [StorageHeaderDeltaError::TooManySlots] if `delta.new_num_slots()` exceeds
+    ///   [AccountStorage::MAX_NUM_STORAGE_SLOTS].
+    /// - Returns [StorageHeaderDeltaError::ChangeIndexOutOfBounds] if a change record's index is
+    ///   not a valid slot index for `delta.new_num_slots()`. A delta read back from a peer (see
+    ///   [Deserializable for StorageHeaderDelta](struct.StorageHeaderDelta.html)) is not otherwise
+    ///   validated against its own `new_num_slots`, so this check is what stands between a
+    ///   corrupted or adversarial delta and a panic.
+    pub fn apply(
+        &self,
+        delta: &StorageHeaderDelta,
+    ) -> Result<AccountStorageHeader, StorageHeaderDeltaError> {
+        if delta.base_num_slots as usize != self.num_slots() {
+            return Err(StorageHeaderDeltaError::BaseMismatch {
+                expected: self.num_slots() as u8,
+                actual: delta.base_num_slots,
+            });
+        }
+        if delta.new_num_slots as usize > AccountStorage::MAX_NUM_STORAGE_SLOTS {
+            return Err(StorageHeaderDeltaError::TooManySlots {
+                max: AccountStorage::MAX_NUM_STORAGE_SLOTS as u8,
+                actual: delta.new_num_slots,
+            });
+        }
+
+        let mut slots: Vec<(StorageSlotType, Word)> = self.slots().cloned().collect();
+        slots.resize(delta.new_num_slots as usize, (StorageSlotType::Value, EMPTY_WORD));
+        for &(index, slot_type, value) in &delta.changes {
+            let slot = slots.get_mut(index as usize).ok_or(
+                StorageHeaderDeltaError::ChangeIndexOutOfBounds {
+                    index,
+                    new_num_slots: delta.new_num_slots,
+                },
+            )?;
+            *slot = (slot_type, value);
+        }
+
+        Ok(AccountStorageHeader::new(slots))
+    }
+}
+
+// SERIALIZATION
+// ================================================================================================
+
+impl Serializable for StorageHeaderDelta {
+    fn write_into<W: ByteWriter>(&self, target: &mut W) {
+        target.write_u8(self.base_num_slots);
+        target.write_u8(self.new_num_slots);
+        target.write_u8(self.changes.len() as u8);
+        for (index, slot_type, value) in &self.changes {
+            target.write_u8(*index);
+            target.write(*slot_type);
+            target.write(value);
+        }
+    }
+}
+
+impl Deserializable for StorageHeaderDelta {
+    fn read_from<R: ByteReader>(source: &mut R) -> Result<Self, DeserializationError> {
+        let base_num_slots = source.read_u8()?;
+        let new_num_slots = source.read_u8()?;
+        let num_changes = source.read_u8()?;
+
+        let mut changes = Vec::with_capacity(num_changes as usize);
+        for _ in 0..num_changes {
+            let index = source.read_u8()?;
+            let slot_type = source.read::<StorageSlotType>()?;
+            let value = source.read::<Word>()?;
+            changes.push((index, slot_type, value));
+        }
+
+        Ok(Self { base_num_slots, new_num_slots, changes })
+    }
+}
+
+// STORAGE HEADER DELTA ERROR
+// ================================================================================================
+
+/// Errors that can occur while applying a [StorageHeaderDelta] to an [AccountStorageHeader].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageHeaderDeltaError {
+    /// `delta.base_num_slots()` does not match the slot count of the header it was applied to --
+    /// the delta was computed against a different version of the header than the one on hand.
+    BaseMismatch { expected: u8, actual: u8 },
+    /// A change record's index is not a valid slot index for `delta.new_num_slots()`. This can
+    /// only happen for a delta that was deserialized from an untrusted source, since [diff] never
+    /// produces one: `read_from` performs no bounds validation on `index` against `new_num_slots`.
+    ///
+    /// [diff]: AccountStorageHeader::diff
+    ChangeIndexOutOfBounds { index: u8, new_num_slots: u8 },
+    /// `delta.new_num_slots()` exceeds [AccountStorage::MAX_NUM_STORAGE_SLOTS].
+    TooManySlots { max: u8, actual: u8 },
+}
+
+impl fmt::Display for StorageHeaderDeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageHeaderDeltaError::BaseMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "storage header delta expects a base header with {actual} slots, but the header being applied to has {expected}"
+                )
+            },
+            StorageHeaderDeltaError::ChangeIndexOutOfBounds { index, new_num_slots } => {
+                write!(
+                    f,
+                    "storage header delta change index {index} is out of bounds for a header of {new_num_slots} slots"
+                )
+            },
+            StorageHeaderDeltaError::TooManySlots { max, actual } => {
+                write!(f, "storage header delta's new slot count {actual} exceeds the maximum of {max}")
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StorageHeaderDeltaError {}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use vm_core::{
+        utils::{Deserializable, Serializable},
+        Felt,
+    };
+
+    use super::AccountStorageHeader;
+    use crate::accounts::StorageSlotType;
+
+    fn word(n: u64) -> vm_core::Word {
+        [Felt::new(n), Felt::new(0), Felt::new(0), Felt::new(0)]
+    }
+
+    #[test]
+    fn test_diff_and_apply_roundtrip() {
+        let base = AccountStorageHeader::new(vec![
+            (StorageSlotType::Value, word(1)),
+            (StorageSlotType::Value, word(2)),
+        ]);
+        let updated = AccountStorageHeader::new(vec![
+            (StorageSlotType::Value, word(1)),
+            (StorageSlotType::Map, word(3)),
+            (StorageSlotType::Value, word(4)),
+        ]);
+
+        let delta = base.diff(&updated);
+        // only the changed slot and the newly-appended slot should be recorded
+        assert_eq!(delta.changes().count(), 2);
+
+        let reconstructed = base.apply(&delta).unwrap();
+        assert_eq!(reconstructed, updated);
+    }
+
+    #[test]
+    fn test_diff_and_apply_truncation() {
+        let base = AccountStorageHeader::new(vec![
+            (StorageSlotType::Value, word(1)),
+            (StorageSlotType::Value, word(2)),
+        ]);
+        let updated = AccountStorageHeader::new(vec![(StorageSlotType::Value, word(1))]);
+
+        let delta = base.diff(&updated);
+        assert_eq!(delta.changes().count(), 0);
+        assert_eq!(delta.new_num_slots(), 1);
+
+        let reconstructed = base.apply(&delta).unwrap();
+        assert_eq!(reconstructed, updated);
+    }
+
+    #[test]
+    fn test_apply_rejects_base_mismatch() {
+        let base = AccountStorageHeader::new(vec![(StorageSlotType::Value, word(1))]);
+        let other_base = AccountStorageHeader::new(vec![
+            (StorageSlotType::Value, word(1)),
+            (StorageSlotType::Value, word(2)),
+        ]);
+        let updated = AccountStorageHeader::new(vec![(StorageSlotType::Value, word(9))]);
+
+        let delta = other_base.diff(&updated);
+        assert_eq!(
+            base.apply(&delta),
+            Err(super::StorageHeaderDeltaError::BaseMismatch { expected: 1, actual: 2 })
+        );
+    }
+
+    #[test]
+    fn test_apply_rejects_out_of_bounds_change_index_instead_of_panicking() {
+        // a delta read back from a peer is not validated against its own new_num_slots on
+        // deserialize, so a corrupted or adversarial change index must be rejected by `apply`
+        // rather than panicking via an out-of-bounds slice index.
+        let base = AccountStorageHeader::new(vec![(StorageSlotType::Value, word(1))]);
+        let delta = super::StorageHeaderDelta {
+            base_num_slots: 1,
+            new_num_slots: 1,
+            changes: vec![(5, StorageSlotType::Value, word(9))],
+        };
+
+        assert_eq!(
+            base.apply(&delta),
+            Err(super::StorageHeaderDeltaError::ChangeIndexOutOfBounds { index: 5, new_num_slots: 1 })
+        );
+    }
+
+    #[test]
+    fn test_serde_storage_header_delta() {
+        let base = AccountStorageHeader::new(vec![(StorageSlotType::Value, word(1))]);
+        let updated =
+            AccountStorageHeader::new(vec![(StorageSlotType::Map, word(2)), (StorageSlotType::Value, word(3))]);
+
+        let delta = base.diff(&updated);
+        let bytes = delta.to_bytes();
+        let deserialized = super::StorageHeaderDelta::read_from_bytes(&bytes).unwrap();
+        assert_eq!(delta, deserialized);
+    }
+}
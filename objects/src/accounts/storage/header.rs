@@ -3,7 +3,7 @@ use alloc::vec::Vec;
 use vm_core::utils::{ByteReader, ByteWriter, Deserializable, Serializable};
 use vm_processor::DeserializationError;
 
-use super::{AccountStorage, StorageSlotType, Word};
+use super::{serde::Context, AccountStorage, StorageSlotType, Word};
 use crate::AccountError;
 
 // ACCOUNT STORAGE HEADER
@@ -55,6 +55,25 @@ impl AccountStorageHeader {
             actual: index as u8,
         })
     }
+
+    // SERIALIZATION
+    // --------------------------------------------------------------------------------------------
+
+    /// Serializes this header in the storage serde format identified by `version`, rather than
+    /// always the current one.
+    ///
+    /// This is used to produce a header payload byte-for-byte identical to what an older node
+    /// would have written, e.g. when testing that this crate still reads state sync'd from one --
+    /// [Serializable::write_into] always writes the current format.
+    ///
+    /// # Errors
+    /// Returns an error if `version` is not a storage serde format known to this crate.
+    pub fn serialize_with_version(&self, version: u8) -> Result<Vec<u8>, DeserializationError> {
+        let context = Context::for_version(version)?;
+        let mut bytes = Vec::new();
+        context.write_header(&self.slots, &mut bytes);
+        Ok(bytes)
+    }
 }
 
 impl From<AccountStorage> for AccountStorageHeader {
@@ -68,16 +87,13 @@ impl From<AccountStorage> for AccountStorageHeader {
 
 impl Serializable for AccountStorageHeader {
     fn write_into<W: ByteWriter>(&self, target: &mut W) {
-        let len = self.slots.len() as u8;
-        target.write_u8(len);
-        target.write_many(self.slots())
+        Context::current().write_header(&self.slots, target);
     }
 }
 
 impl Deserializable for AccountStorageHeader {
     fn read_from<R: ByteReader>(source: &mut R) -> Result<Self, DeserializationError> {
-        let len = source.read_u8()?;
-        let slots = source.read_many(len as usize)?;
+        let slots = Context::read_header(source)?;
         // number of storage slots is guaranteed to be smaller than or equal to 255
         Ok(Self::new(slots))
     }
@@ -132,4 +148,40 @@ mod tests {
         // assert deserialized == storage header
         assert_eq!(storage_header, deserialized);
     }
+
+    #[test]
+    fn test_serde_account_storage_header_legacy_fallback() {
+        // a header serialized under the legacy (untagged) format must still deserialize
+        let storage_header = AccountStorageHeader::from(AccountStorage::mock());
+
+        let legacy_bytes = storage_header.serialize_with_version(0).unwrap();
+        let deserialized = AccountStorageHeader::read_from_bytes(&legacy_bytes).unwrap();
+        assert_eq!(storage_header, deserialized);
+
+        // the current format round-trips through the public Serializable impl as well
+        let current_bytes = storage_header.to_bytes();
+        assert_ne!(legacy_bytes, current_bytes);
+        assert_eq!(storage_header, AccountStorageHeader::read_from_bytes(&current_bytes).unwrap());
+    }
+
+    #[test]
+    fn test_serde_account_storage_header_legacy_fallback_at_255_slots() {
+        // a legacy header with exactly 255 slots serializes its slot count as 0xff, the same
+        // leading byte used to mark a current-format payload -- make sure it still round-trips
+        // as the 255-slot legacy header it is, rather than being misparsed as versioned.
+        let slots: Vec<_> = (0..255)
+            .map(|i| (StorageSlotType::Value, [Felt::new(i), Felt::new(0), Felt::new(0), Felt::new(0)]))
+            .collect();
+        let storage_header = AccountStorageHeader::new(slots);
+
+        let legacy_bytes = storage_header.serialize_with_version(0).unwrap();
+        assert_eq!(legacy_bytes[0], 0xff);
+        let deserialized = AccountStorageHeader::read_from_bytes(&legacy_bytes).unwrap();
+        assert_eq!(storage_header, deserialized);
+
+        // the current format round-trips as well, and is distinguishable from the legacy bytes
+        let current_bytes = storage_header.to_bytes();
+        assert_ne!(legacy_bytes, current_bytes);
+        assert_eq!(storage_header, AccountStorageHeader::read_from_bytes(&current_bytes).unwrap());
+    }
 }
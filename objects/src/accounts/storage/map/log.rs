@@ -0,0 +1,389 @@
+//! Append-only, log-structured persistence for large [StorageMap] slots.
+//!
+//! `StorageMap` itself always keeps its entries on the heap (see [StorageMap::with_entries]); the
+//! backend in this module is an opt-in companion for accounts whose maps are large enough that
+//! repeatedly loading and re-serializing them in full becomes expensive. It is only compiled with
+//! the `std` feature, since it needs a real filesystem.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use vm_core::utils::{Deserializable, Serializable};
+
+use super::StorageMap;
+use crate::Word;
+
+// CONSTANTS
+// ================================================================================================
+
+/// Size, in bytes, of a serialized [Word] ([Felt] is an 8-byte field element).
+const WORD_SIZE: usize = 32;
+
+/// Size, in bytes, of a single append-only log record: `key || value || write_version`.
+const RECORD_SIZE: usize = WORD_SIZE * 2 + 8;
+
+// LOG STORAGE MAP BACKEND
+// ================================================================================================
+
+/// An append-only, log-structured file backend for a [StorageMap].
+///
+/// Every committed mutation is appended to the backing file as a fixed-size
+/// `(key, value, write_version)` record; existing bytes are never rewritten. A monotonically
+/// increasing `write_version` counter is stamped on each record, and an in-memory `key -> record
+/// offset` index is kept so that, for keys written more than once, the newest record wins on
+/// read. The map's Merkle root is recovered by replaying this index into a [StorageMap] (see
+/// [StorageMap::open_log]).
+///
+/// Reads and writes go through plain [File] I/O (`seek`/`read_exact`/`write_all`) against a
+/// single shared cursor -- this backend does not memory-map the file.
+///
+/// # Concurrency limitations
+/// This is a single-process, `&mut self` API: because reads seek the same file handle, two
+/// [LogStorageMapBackend] methods cannot run concurrently even on one handle without external
+/// synchronization, and there is no support for a separate writer process's appends becoming
+/// visible to an already-open reader. A real single-writer/many-reader backend (e.g. one backed
+/// by an actual memory map) is future work; this type only delivers the on-disk log format and
+/// in-process replay/compaction logic.
+///
+/// Use [LogStorageMapBackend::compact] periodically to reclaim space taken by superseded records:
+/// it rewrites only the live (newest-per-key) entries into a fresh file.
+pub struct LogStorageMapBackend {
+    path: PathBuf,
+    file: File,
+    /// Record offset (in units of [RECORD_SIZE], from the start of the file) of the newest entry
+    /// written for each key, keyed by the key's canonical serialized bytes.
+    index: BTreeMap<[u8; WORD_SIZE], u64>,
+    next_write_version: u64,
+}
+
+impl LogStorageMapBackend {
+    // CONSTRUCTORS
+    // --------------------------------------------------------------------------------------------
+
+    /// Opens the append-only log at `path`, creating it if it does not yet exist, and rebuilds
+    /// the in-memory index by replaying every record in the file.
+    ///
+    /// # Errors
+    /// Returns an error if `path` cannot be opened or contains a truncated (partial) record.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(&path)?;
+
+        let (index, next_write_version) = Self::replay(&mut file)?;
+
+        Ok(Self { path, file, index, next_write_version })
+    }
+
+    /// Replays every record in `file` from the start, returning the newest-offset-per-key index
+    /// and the write version to resume from.
+    fn replay(file: &mut File) -> io::Result<(BTreeMap<[u8; WORD_SIZE], u64>, u64)> {
+        if file.metadata()?.len() % RECORD_SIZE as u64 != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "log ends in a truncated record"));
+        }
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut index = BTreeMap::new();
+        let mut next_write_version = 0u64;
+        let mut record = [0u8; RECORD_SIZE];
+        let mut offset = 0u64;
+
+        loop {
+            match file.read_exact(&mut record) {
+                Ok(()) => {},
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+
+            let mut key = [0u8; WORD_SIZE];
+            key.copy_from_slice(&record[..WORD_SIZE]);
+            let write_version = u64::from_le_bytes(record[RECORD_SIZE - 8..].try_into().unwrap());
+
+            // the newest record for each key wins: later offsets overwrite earlier ones as we
+            // replay from the start of the file
+            index.insert(key, offset);
+            next_write_version = next_write_version.max(write_version + 1);
+            offset += 1;
+        }
+
+        Ok((index, next_write_version))
+    }
+
+    // ACCESSORS
+    // --------------------------------------------------------------------------------------------
+
+    /// Returns the path of the backing file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the value most recently written for `key`, or `None` if it has no live entry.
+    ///
+    /// # Errors
+    /// Returns an error if the backing file cannot be read.
+    pub fn get(&mut self, key: &Word) -> io::Result<Option<Word>> {
+        let Some(&offset) = self.index.get(&word_key(key)) else {
+            return Ok(None);
+        };
+
+        let record = self.read_record(offset)?;
+        Ok(Some(decode_word(&record[WORD_SIZE..WORD_SIZE * 2])?))
+    }
+
+    /// Returns every live `(key, value)` pair, i.e. the newest record for each key.
+    ///
+    /// This is primarily used to rebuild an in-memory [StorageMap] from the log; see
+    /// [StorageMap::open_log].
+    ///
+    /// # Errors
+    /// Returns an error if the backing file cannot be read.
+    pub fn live_entries(&mut self) -> io::Result<Vec<(Word, Word)>> {
+        let offsets: Vec<u64> = self.index.values().copied().collect();
+        let mut entries = Vec::with_capacity(offsets.len());
+
+        for offset in offsets {
+            let record = self.read_record(offset)?;
+            let key = decode_word(&record[..WORD_SIZE])?;
+            let value = decode_word(&record[WORD_SIZE..WORD_SIZE * 2])?;
+            entries.push((key, value));
+        }
+
+        Ok(entries)
+    }
+
+    // MUTATORS
+    // --------------------------------------------------------------------------------------------
+
+    /// Appends a new `(key, value)` record, superseding any prior record for `key`.
+    ///
+    /// Returns the `write_version` stamped on the new record.
+    ///
+    /// # Errors
+    /// Returns an error if the record cannot be appended to the backing file.
+    pub fn append(&mut self, key: Word, value: Word) -> io::Result<u64> {
+        let write_version = self.next_write_version;
+
+        let mut record = [0u8; RECORD_SIZE];
+        record[..WORD_SIZE].copy_from_slice(&key.to_bytes());
+        record[WORD_SIZE..WORD_SIZE * 2].copy_from_slice(&value.to_bytes());
+        record[RECORD_SIZE - 8..].copy_from_slice(&write_version.to_le_bytes());
+        self.file.write_all(&record)?;
+        self.file.flush()?;
+
+        // the record we just appended is the last one in the file; point the index at it,
+        // superseding whatever offset (if any) was previously recorded for this key
+        let appended_offset = self.record_count()? - 1;
+        self.index.insert(word_key(&key), appended_offset);
+
+        self.next_write_version = write_version + 1;
+        Ok(write_version)
+    }
+
+    /// Rewrites the backing file so that it contains only live entries (the newest record per
+    /// key), dropping every superseded record.
+    ///
+    /// # Errors
+    /// Returns an error if the live entries cannot be read or the replacement file cannot be
+    /// written.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let offsets: Vec<u64> = self.index.values().copied().collect();
+
+        let tmp_path = self.path.with_extension("compact.tmp");
+        let mut tmp_file =
+            OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&tmp_path)?;
+
+        // live records are copied unchanged, so each keeps its write_version and the counter
+        // resumes from the same value after compaction or a later replay
+        let mut index = BTreeMap::new();
+        for (new_offset, offset) in offsets.into_iter().enumerate() {
+            let record = self.read_record(offset)?;
+            let key = decode_word(&record[..WORD_SIZE])?;
+            decode_word(&record[WORD_SIZE..WORD_SIZE * 2])?;
+            tmp_file.write_all(&record)?;
+            index.insert(word_key(&key), new_offset as u64);
+        }
+        tmp_file.flush()?;
+
+        std::fs::rename(&tmp_path, &self.path)?;
+        self.file = OpenOptions::new().read(true).append(true).open(&self.path)?;
+        self.index = index;
+
+        Ok(())
+    }
+
+    // HELPERS
+    // --------------------------------------------------------------------------------------------
+
+    fn read_record(&mut self, offset: u64) -> io::Result<[u8; RECORD_SIZE]> {
+        let mut record = [0u8; RECORD_SIZE];
+        self.file.seek(SeekFrom::Start(offset * RECORD_SIZE as u64))?;
+        self.file.read_exact(&mut record)?;
+        Ok(record)
+    }
+
+    fn record_count(&mut self) -> io::Result<u64> {
+        let len = self.file.metadata()?.len();
+        Ok(len / RECORD_SIZE as u64)
+    }
+}
+
+/// Returns `key`'s canonical serialized bytes, used as the in-memory index's key.
+fn word_key(key: &Word) -> [u8; WORD_SIZE] {
+    let mut bytes = [0u8; WORD_SIZE];
+    bytes.copy_from_slice(&key.to_bytes());
+    bytes
+}
+
+/// Decodes a [Word] from a record slice produced by [Word::to_bytes].
+///
+/// # Errors
+/// Returns an error if `bytes` does not decode to a valid [Word]. This can happen even for a
+/// record whose length passed [LogStorageMapBackend::replay]'s truncation check, e.g. a
+/// length-complete but bit-flipped record left behind by a torn write -- replay only detects
+/// missing bytes, not corrupted ones, so this is the backstop that turns that corruption into an
+/// `Err` instead of a panic.
+fn decode_word(bytes: &[u8]) -> io::Result<Word> {
+    Word::read_from_bytes(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// STORAGE MAP INTEGRATION
+// ================================================================================================
+
+impl StorageMap {
+    /// Opens (or creates) a [LogStorageMapBackend] at `path` and rebuilds a [StorageMap] from its
+    /// live entries.
+    ///
+    /// The returned map is a regular in-memory [StorageMap]: its `root()` and serialization
+    /// behave exactly as if it had been built with [StorageMap::with_entries]. The returned
+    /// backend is what future mutations should be appended to via [LogStorageMapBackend::append]
+    /// before being applied to the in-memory map, keeping the two in sync.
+    ///
+    /// # Errors
+    /// Returns an error if the backing file cannot be opened or replayed.
+    pub fn open_log(path: impl AsRef<Path>) -> io::Result<(Self, LogStorageMapBackend)> {
+        let mut backend = LogStorageMapBackend::open(path)?;
+        let entries = backend.live_entries()?;
+        let map = StorageMap::with_entries(entries)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok((map, backend))
+    }
+}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::LogStorageMapBackend;
+    use crate::Word;
+
+    fn word(n: u64) -> Word {
+        use vm_core::Felt;
+        [Felt::new(n), Felt::new(0), Felt::new(0), Felt::new(0)]
+    }
+
+    /// A path under the OS temp dir unique to this test process and the given label, so
+    /// concurrent test runs don't clash; the backing file is removed when it drops.
+    struct TempLogPath(std::path::PathBuf);
+
+    impl TempLogPath {
+        fn new(label: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("miden-storage-log-test-{label}-{}.log", std::process::id()));
+            Self(path)
+        }
+    }
+
+    impl Drop for TempLogPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_open_append_get_roundtrip() {
+        let path = TempLogPath::new("roundtrip");
+        let mut backend = LogStorageMapBackend::open(&path.0).unwrap();
+
+        assert_eq!(backend.get(&word(1)).unwrap(), None);
+
+        backend.append(word(1), word(100)).unwrap();
+        assert_eq!(backend.get(&word(1)).unwrap(), Some(word(100)));
+
+        // re-opening the log replays it back into the same state
+        drop(backend);
+        let mut reopened = LogStorageMapBackend::open(&path.0).unwrap();
+        assert_eq!(reopened.get(&word(1)).unwrap(), Some(word(100)));
+    }
+
+    #[test]
+    fn test_replay_picks_newest_record_per_key() {
+        let path = TempLogPath::new("newest-wins");
+        let mut backend = LogStorageMapBackend::open(&path.0).unwrap();
+
+        backend.append(word(1), word(10)).unwrap();
+        backend.append(word(2), word(20)).unwrap();
+        backend.append(word(1), word(11)).unwrap();
+
+        drop(backend);
+        let mut reopened = LogStorageMapBackend::open(&path.0).unwrap();
+        assert_eq!(reopened.get(&word(1)).unwrap(), Some(word(11)));
+        assert_eq!(reopened.get(&word(2)).unwrap(), Some(word(20)));
+
+        let mut entries = reopened.live_entries().unwrap();
+        entries.sort_by_key(|(key, _)| *key);
+        assert_eq!(entries, vec![(word(1), word(11)), (word(2), word(20))]);
+    }
+
+    #[test]
+    fn test_compact_preserves_live_entries_and_drops_superseded() {
+        let path = TempLogPath::new("compact");
+        let mut backend = LogStorageMapBackend::open(&path.0).unwrap();
+
+        backend.append(word(1), word(10)).unwrap();
+        backend.append(word(2), word(20)).unwrap();
+        backend.append(word(1), word(11)).unwrap();
+
+        let size_before_compact = std::fs::metadata(&path.0).unwrap().len();
+        backend.compact().unwrap();
+        let size_after_compact = std::fs::metadata(&path.0).unwrap().len();
+
+        // the superseded record for key 1 is gone, so the file shrinks from 3 records to 2
+        assert!(size_after_compact < size_before_compact);
+
+        assert_eq!(backend.get(&word(1)).unwrap(), Some(word(11)));
+        assert_eq!(backend.get(&word(2)).unwrap(), Some(word(20)));
+
+        let mut entries = backend.live_entries().unwrap();
+        entries.sort_by_key(|(key, _)| *key);
+        assert_eq!(entries, vec![(word(1), word(11)), (word(2), word(20))]);
+    }
+
+    #[test]
+    fn test_get_errors_instead_of_panicking_on_corrupted_record() {
+        use std::io::Write;
+
+        let path = TempLogPath::new("corrupted-record");
+        let mut backend = LogStorageMapBackend::open(&path.0).unwrap();
+        backend.append(word(1), word(10)).unwrap();
+        drop(backend);
+
+        // flip every byte of the record's value, leaving its key intact so the index still finds
+        // it: length-complete, so replay()'s truncation check doesn't catch it, but the value is
+        // no longer a valid encoding of anything
+        let mut corrupted = std::fs::read(&path.0).unwrap();
+        for byte in &mut corrupted[super::WORD_SIZE..super::WORD_SIZE * 2] {
+            *byte = !*byte;
+        }
+        std::fs::File::create(&path.0).unwrap().write_all(&corrupted).unwrap();
+
+        let mut reopened = LogStorageMapBackend::open(&path.0).unwrap();
+        assert!(reopened.get(&word(1)).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+use alloc::{format, vec::Vec};
+
+use vm_core::utils::{ByteReader, ByteWriter, Deserializable, Serializable};
+use vm_processor::DeserializationError;
+
+use super::{StorageSlot, StorageSlotType, Word};
+
+// STORAGE SERDE VERSIONING
+// ================================================================================================
+
+/// First byte of the envelope written before every current-format storage payload.
+///
+/// A legacy [StorageSlot] payload begins with a [StorageSlotType] discriminant, which is always
+/// `0` or `1` and therefore never collides with this value on its own. A legacy
+/// [super::AccountStorageHeader] payload begins with a slot count, which *can* legitimately be
+/// `0xff` (a header with 255 slots) -- see [MAGIC] for how that case is told apart from a real
+/// envelope without misreading one as the other.
+const SENTINEL: u8 = 0xff;
+
+/// Second byte of the envelope, read only once [SENTINEL] has been seen as the first byte.
+///
+/// This only matters for headers: a legacy header with exactly 255 slots serializes its length
+/// byte as `0xff` (== [SENTINEL]), and the byte that immediately follows it in that case is
+/// always the first slot's [StorageSlotType] discriminant (`0` or `1`). By reserving a value
+/// outside that range as the envelope's second byte, [Context::read_header] can always tell a
+/// 255-slot legacy header from a current-format payload, no matter how many slots the legacy
+/// header has.
+const MAGIC: u8 = 0xfe;
+
+/// The legacy, untagged storage serde format, still read and (via `serialize_with_version`)
+/// written by this crate.
+pub const STORAGE_SERDE_VERSION_LEGACY: u8 = 0;
+
+/// The storage serde format written by [CurrentContext] and understood by this crate.
+pub const STORAGE_SERDE_VERSION_1: u8 = 1;
+
+/// Size, in bytes, of the envelope [Context::write_envelope] writes before a current-format
+/// payload ([SENTINEL], [MAGIC], then the version byte). Callers computing a size hint for data
+/// serialized via [Context::current] (i.e. everything written through the public [Serializable]
+/// impls) need to account for this on top of the payload body's own size hint.
+pub(super) const ENVELOPE_LEN: usize = 3;
+
+/// A storage (de)serialization format.
+///
+/// Implementors encode/decode the body of a [StorageSlot] and the slot list of an
+/// [super::AccountStorageHeader] -- i.e. everything after the envelope that [Context] reads/writes
+/// on their behalf. [LegacyContext] reproduces the original, untagged layout used before format
+/// versioning existed; [CurrentContext] is the current, explicitly versioned one. Introducing a
+/// new on-disk layout means adding another implementer here and a matching arm in
+/// [Context::for_version].
+pub(super) trait StorageSerdeContext {
+    /// Writes a storage slot's body into `target`.
+    fn write_slot<W: ByteWriter>(&self, slot: &StorageSlot, target: &mut W);
+
+    /// Reads a storage slot's body from `source`, with no part of it already consumed.
+    fn read_slot<R: ByteReader>(&self, source: &mut R) -> Result<StorageSlot, DeserializationError>;
+
+    /// Writes a storage header's slot list into `target`.
+    fn write_header<W: ByteWriter>(&self, slots: &[(StorageSlotType, Word)], target: &mut W);
+
+    /// Reads a storage header's slot list from `source`, with no part of it already consumed.
+    fn read_header<R: ByteReader>(
+        &self,
+        source: &mut R,
+    ) -> Result<Vec<(StorageSlotType, Word)>, DeserializationError>;
+}
+
+// LEGACY CONTEXT
+// ================================================================================================
+
+/// Reproduces the original, untagged storage encoding used before format versioning was
+/// introduced: a [StorageSlot] is its type discriminant followed by its value, and a header is a
+/// slot count followed by that many `(type, value)` pairs.
+pub(super) struct LegacyContext;
+
+impl LegacyContext {
+    /// Reads a storage slot's body given that its type discriminant has already been consumed
+    /// from `source` and decoded into `slot_type`.
+    fn read_slot_with_type<R: ByteReader>(
+        &self,
+        slot_type: StorageSlotType,
+        source: &mut R,
+    ) -> Result<StorageSlot, DeserializationError> {
+        match slot_type {
+            StorageSlotType::Value => Ok(StorageSlot::Value(source.read()?)),
+            StorageSlotType::Map => Ok(StorageSlot::Map(source.read()?)),
+        }
+    }
+
+    /// Reads `len` slots given that `len` has already been consumed from `source` as the slot
+    /// count.
+    fn read_header_with_len<R: ByteReader>(
+        &self,
+        len: usize,
+        source: &mut R,
+    ) -> Result<Vec<(StorageSlotType, Word)>, DeserializationError> {
+        source.read_many(len)
+    }
+
+    /// Reads `len` slots given that `len` and the first slot's type discriminant have already
+    /// been consumed from `source` and decoded into `first_slot_type`.
+    ///
+    /// This is the one case where a legacy payload and a current-format envelope are
+    /// indistinguishable after a single byte (see [MAGIC]): by the time the caller finds out it
+    /// was looking at a legacy, 255-slot header after all, it has already consumed the first
+    /// slot's type byte while checking for the envelope's magic byte.
+    fn read_header_with_len_and_first_type<R: ByteReader>(
+        &self,
+        len: usize,
+        first_slot_type: StorageSlotType,
+        source: &mut R,
+    ) -> Result<Vec<(StorageSlotType, Word)>, DeserializationError> {
+        let first_value = source.read::<Word>()?;
+        let mut slots = Vec::with_capacity(len);
+        slots.push((first_slot_type, first_value));
+        if len > 1 {
+            slots.extend(source.read_many::<(StorageSlotType, Word)>(len - 1)?);
+        }
+        Ok(slots)
+    }
+}
+
+impl StorageSerdeContext for LegacyContext {
+    fn write_slot<W: ByteWriter>(&self, slot: &StorageSlot, target: &mut W) {
+        target.write(slot.slot_type());
+        match slot {
+            StorageSlot::Value(value) => target.write(value),
+            StorageSlot::Map(map) => target.write(map),
+        }
+    }
+
+    fn read_slot<R: ByteReader>(&self, source: &mut R) -> Result<StorageSlot, DeserializationError> {
+        let slot_type = source.read::<StorageSlotType>()?;
+        self.read_slot_with_type(slot_type, source)
+    }
+
+    fn write_header<W: ByteWriter>(&self, slots: &[(StorageSlotType, Word)], target: &mut W) {
+        target.write_u8(slots.len() as u8);
+        target.write_many(slots);
+    }
+
+    fn read_header<R: ByteReader>(
+        &self,
+        source: &mut R,
+    ) -> Result<Vec<(StorageSlotType, Word)>, DeserializationError> {
+        let len = source.read_u8()?;
+        self.read_header_with_len(len as usize, source)
+    }
+}
+
+// CURRENT CONTEXT
+// ================================================================================================
+
+/// The current, versioned storage encoding ([STORAGE_SERDE_VERSION_1]).
+///
+/// The slot and header bodies are unchanged from [LegacyContext] for now; what [Context] adds
+/// around them is the three-byte envelope ([SENTINEL], [MAGIC], then the version byte) that
+/// [Context::read_slot]/[Context::read_header] use to tell a current-format payload apart from a
+/// legacy one. Future layout changes (e.g. a new [StorageSlotType] variant or extra map metadata)
+/// bump [STORAGE_SERDE_VERSION_1] and land in a new context here, while [LegacyContext] keeps
+/// reading data produced by older nodes.
+pub(super) struct CurrentContext;
+
+impl StorageSerdeContext for CurrentContext {
+    fn write_slot<W: ByteWriter>(&self, slot: &StorageSlot, target: &mut W) {
+        LegacyContext.write_slot(slot, target)
+    }
+
+    fn read_slot<R: ByteReader>(&self, source: &mut R) -> Result<StorageSlot, DeserializationError> {
+        LegacyContext.read_slot(source)
+    }
+
+    fn write_header<W: ByteWriter>(&self, slots: &[(StorageSlotType, Word)], target: &mut W) {
+        LegacyContext.write_header(slots, target)
+    }
+
+    fn read_header<R: ByteReader>(
+        &self,
+        source: &mut R,
+    ) -> Result<Vec<(StorageSlotType, Word)>, DeserializationError> {
+        LegacyContext.read_header(source)
+    }
+}
+
+// CONTEXT DISPATCH
+// ================================================================================================
+
+/// Selects the [StorageSerdeContext] a payload was written with.
+pub(super) enum Context {
+    Legacy(LegacyContext),
+    Current(CurrentContext),
+}
+
+impl Context {
+    /// Returns the context this crate currently writes new storage payloads with.
+    pub(super) fn current() -> Self {
+        Self::Current(CurrentContext)
+    }
+
+    /// Returns the context that writes/reads the given version, or an error if `version` is not
+    /// recognized by this build of the crate.
+    pub(super) fn for_version(version: u8) -> Result<Self, DeserializationError> {
+        match version {
+            STORAGE_SERDE_VERSION_LEGACY => Ok(Self::Legacy(LegacyContext)),
+            STORAGE_SERDE_VERSION_1 => Ok(Self::Current(CurrentContext)),
+            other => Err(DeserializationError::InvalidValue(format!(
+                "unsupported storage serde format version {other}"
+            ))),
+        }
+    }
+
+    /// Writes the envelope identifying this context, if any (a legacy context writes nothing,
+    /// matching the untagged layout it reproduces).
+    pub(super) fn write_envelope<W: ByteWriter>(&self, target: &mut W) {
+        if let Self::Current(_) = self {
+            target.write_u8(SENTINEL);
+            target.write_u8(MAGIC);
+            target.write_u8(STORAGE_SERDE_VERSION_1);
+        }
+    }
+
+    /// Writes a storage slot, including its envelope.
+    pub(super) fn write_slot<W: ByteWriter>(&self, slot: &StorageSlot, target: &mut W) {
+        self.write_envelope(target);
+        match self {
+            Self::Legacy(ctx) => ctx.write_slot(slot, target),
+            Self::Current(ctx) => ctx.write_slot(slot, target),
+        }
+    }
+
+    /// Writes a storage header's slot list, including its envelope.
+    pub(super) fn write_header<W: ByteWriter>(&self, slots: &[(StorageSlotType, Word)], target: &mut W) {
+        self.write_envelope(target);
+        match self {
+            Self::Legacy(ctx) => ctx.write_header(slots, target),
+            Self::Current(ctx) => ctx.write_header(slots, target),
+        }
+    }
+
+    /// Reads a [StorageSlot], determining from its leading byte(s) whether it is legacy or
+    /// current-format.
+    ///
+    /// A [StorageSlot]'s legacy leading byte is a [StorageSlotType] discriminant (`0` or `1`),
+    /// which can never be [SENTINEL], so one byte of lookahead is always enough here.
+    pub(super) fn read_slot<R: ByteReader>(source: &mut R) -> Result<StorageSlot, DeserializationError> {
+        let first = source.read_u8()?;
+        if first != SENTINEL {
+            return LegacyContext.read_slot_with_type(slot_type_from_byte(first)?, source);
+        }
+
+        let version = Self::read_envelope_after_sentinel(source)?;
+        match Self::for_version(version)? {
+            Self::Legacy(ctx) => ctx.read_slot(source),
+            Self::Current(ctx) => ctx.read_slot(source),
+        }
+    }
+
+    /// Reads an [super::AccountStorageHeader]'s slot list, determining from its leading byte(s)
+    /// whether it is legacy or current-format.
+    ///
+    /// A header's legacy leading byte is a slot count, which can legitimately be [SENTINEL]
+    /// (255 slots); see [MAGIC] for how that case is disambiguated from a real envelope.
+    pub(super) fn read_header<R: ByteReader>(
+        source: &mut R,
+    ) -> Result<Vec<(StorageSlotType, Word)>, DeserializationError> {
+        let first = source.read_u8()?;
+        if first != SENTINEL {
+            return LegacyContext.read_header_with_len(first as usize, source);
+        }
+
+        let second = source.read_u8()?;
+        if second != MAGIC {
+            // `first` (0xff) was a legacy slot count of 255, and `second` is already the first
+            // slot's type discriminant, consumed while checking for the envelope's magic byte.
+            let first_slot_type = slot_type_from_byte(second)?;
+            return LegacyContext.read_header_with_len_and_first_type(255, first_slot_type, source);
+        }
+
+        let version = source.read_u8()?;
+        match Self::for_version(version)? {
+            Self::Legacy(ctx) => ctx.read_header(source),
+            Self::Current(ctx) => ctx.read_header(source),
+        }
+    }
+
+    /// Reads the version byte that follows [SENTINEL] and [MAGIC] in a current-format envelope,
+    /// given that [SENTINEL] has already been consumed as the first byte.
+    fn read_envelope_after_sentinel<R: ByteReader>(source: &mut R) -> Result<u8, DeserializationError> {
+        let magic = source.read_u8()?;
+        if magic != MAGIC {
+            return Err(DeserializationError::InvalidValue(format!(
+                "malformed storage serde envelope: expected magic byte {MAGIC:#x}, got {magic:#x}"
+            )));
+        }
+        source.read_u8()
+    }
+}
+
+/// Decodes a [StorageSlotType] from its raw one-byte discriminant.
+fn slot_type_from_byte(byte: u8) -> Result<StorageSlotType, DeserializationError> {
+    match byte {
+        0 => Ok(StorageSlotType::Value),
+        1 => Ok(StorageSlotType::Map),
+        other => Err(DeserializationError::InvalidValue(format!(
+            "invalid storage slot type discriminant {other}"
+        ))),
+    }
+}
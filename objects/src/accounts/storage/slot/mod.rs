@@ -1,10 +1,16 @@
+use alloc::vec::Vec;
+
 use vm_core::{
     utils::{ByteReader, ByteWriter, Deserializable, Serializable},
     EMPTY_WORD, ZERO,
 };
 use vm_processor::DeserializationError;
 
-use super::{map::EMPTY_STORAGE_MAP_ROOT, Felt, StorageMap, Word};
+use super::{
+    map::EMPTY_STORAGE_MAP_ROOT,
+    serde::{Context, ENVELOPE_LEN},
+    Felt, StorageMap, Word,
+};
 
 mod r#type;
 pub use r#type::StorageSlotType;
@@ -81,6 +87,24 @@ impl StorageSlot {
             StorageSlot::Map(_) => StorageSlotType::Map,
         }
     }
+
+    /// Serializes this slot in the storage serde format identified by `version`, rather than
+    /// always the current one.
+    ///
+    /// Node software that has to interoperate with peers running an older version of the crate
+    /// can use this to produce a payload in the exact wire format that peer expects, instead of
+    /// relying on [Serializable::write_into] (which always writes [STORAGE_SERDE_VERSION_1]).
+    ///
+    /// [STORAGE_SERDE_VERSION_1]: super::serde::STORAGE_SERDE_VERSION_1
+    ///
+    /// # Errors
+    /// Returns an error if `version` is not a storage serde format known to this crate.
+    pub fn serialize_with_version(&self, version: u8) -> Result<Vec<u8>, DeserializationError> {
+        let context = Context::for_version(version)?;
+        let mut bytes = Vec::new();
+        context.write_slot(self, &mut bytes);
+        Ok(bytes)
+    }
 }
 
 // SERIALIZATION
@@ -88,16 +112,14 @@ impl StorageSlot {
 
 impl Serializable for StorageSlot {
     fn write_into<W: ByteWriter>(&self, target: &mut W) {
-        target.write(self.slot_type());
-
-        match self {
-            Self::Value(value) => target.write(value),
-            Self::Map(map) => target.write(map),
-        }
+        Context::current().write_slot(self, target);
     }
 
     fn get_size_hint(&self) -> usize {
-        let mut size = self.slot_type().get_size_hint();
+        // write_into always goes through Context::current(), which prefixes every slot with the
+        // 3-byte envelope (see Context::write_envelope) ahead of the slot-type and value bytes
+        // summed below.
+        let mut size = ENVELOPE_LEN + self.slot_type().get_size_hint();
 
         size += match self {
             StorageSlot::Value(word) => word.get_size_hint(),
@@ -110,18 +132,7 @@ impl Serializable for StorageSlot {
 
 impl Deserializable for StorageSlot {
     fn read_from<R: ByteReader>(source: &mut R) -> Result<Self, DeserializationError> {
-        let storage_slot_type = source.read::<StorageSlotType>()?;
-
-        match storage_slot_type {
-            StorageSlotType::Value => {
-                let word = source.read::<Word>()?;
-                Ok(StorageSlot::Value(word))
-            },
-            StorageSlotType::Map => {
-                let map = source.read::<StorageMap>()?;
-                Ok(StorageSlot::Map(map))
-            },
-        }
+        Context::read_slot(source)
     }
 }
 
@@ -130,8 +141,12 @@ impl Deserializable for StorageSlot {
 
 #[cfg(test)]
 mod tests {
-    use vm_core::utils::{Deserializable, Serializable};
+    use vm_core::{
+        utils::{Deserializable, Serializable},
+        EMPTY_WORD,
+    };
 
+    use super::StorageSlot;
     use crate::accounts::AccountStorage;
 
     #[test]
@@ -141,4 +156,29 @@ mod tests {
         let deserialized = AccountStorage::read_from_bytes(&serialized).unwrap();
         assert_eq!(deserialized, storage)
     }
+
+    #[test]
+    fn test_serde_storage_slot_legacy_fallback() {
+        // a slot serialized under the legacy (untagged) format must still deserialize
+        let slot = StorageSlot::Value(EMPTY_WORD);
+
+        let legacy_bytes = slot.serialize_with_version(0).unwrap();
+        assert_eq!(slot, StorageSlot::read_from_bytes(&legacy_bytes).unwrap());
+
+        // the current format round-trips through the public Serializable impl as well
+        let current_bytes = slot.to_bytes();
+        assert_ne!(legacy_bytes, current_bytes);
+        assert_eq!(slot, StorageSlot::read_from_bytes(&current_bytes).unwrap());
+    }
+
+    #[test]
+    fn test_storage_slot_size_hint_matches_write_into() {
+        // get_size_hint must account for the 3-byte envelope write_into always prefixes a slot
+        // with, not just the slot-type and value bytes that follow it
+        let value_slot = StorageSlot::Value(EMPTY_WORD);
+        assert_eq!(value_slot.get_size_hint(), value_slot.to_bytes().len());
+
+        let map_slot = StorageSlot::empty_map();
+        assert_eq!(map_slot.get_size_hint(), map_slot.to_bytes().len());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,299 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use vm_core::utils::{ByteReader, ByteWriter, Deserializable, Serializable};
+use vm_processor::DeserializationError;
+
+use super::AccountStorage;
+use crate::{
+    accounts::{AccountHeader, AccountId},
+    Digest,
+};
+
+// CONSTANTS
+// ================================================================================================
+
+/// The snapshot archive format written by [Snapshot::write_to] and understood by
+/// [Snapshot::read_from].
+pub const SNAPSHOT_FORMAT_VERSION_1: u8 = 1;
+
+/// The maximum number of accounts a single snapshot may package.
+///
+/// [Snapshot::read_from] rejects a manifest claiming more than this before allocating anything
+/// for it, so a corrupted or adversarial `num_accounts` (e.g. state-sync data from an untrusted
+/// peer) can't be used to make the reader reserve an unbounded amount of memory up front.
+pub const MAX_ACCOUNTS_PER_SNAPSHOT: usize = 1_000_000;
+
+// SNAPSHOT
+// ================================================================================================
+
+/// An archive of the full storage state of a set of accounts.
+///
+/// A snapshot packages, for each account: an [AccountHeader] (the account's id, nonce, vault
+/// root, storage commitment, and code commitment, as returned by `parse_final_account_header`),
+/// followed by that account's [AccountStorage]. Together these form a manifest plus payload that
+/// can be written to and read back from a single artifact, enabling fast state-sync and
+/// backup/restore of account state without replaying transactions.
+///
+/// On [Snapshot::read_from], every account's deserialized storage is checked against the
+/// `storage_commitment` recorded for it in the manifest; a mismatch is reported via
+/// [SnapshotError::StorageCommitmentMismatch] naming the offending [AccountId].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    accounts: Vec<(AccountHeader, AccountStorage)>,
+}
+
+impl Snapshot {
+    // CONSTRUCTOR
+    // --------------------------------------------------------------------------------------------
+
+    /// Returns a new snapshot packaging the given accounts' headers and storage.
+    pub fn new(accounts: Vec<(AccountHeader, AccountStorage)>) -> Self {
+        Self { accounts }
+    }
+
+    // PUBLIC ACCESSORS
+    // --------------------------------------------------------------------------------------------
+
+    /// Returns an iterator over the accounts packaged in this snapshot.
+    pub fn accounts(&self) -> impl Iterator<Item = &(AccountHeader, AccountStorage)> {
+        self.accounts.iter()
+    }
+
+    /// Returns the number of accounts packaged in this snapshot.
+    pub fn num_accounts(&self) -> usize {
+        self.accounts.len()
+    }
+
+    // SERIALIZATION
+    // --------------------------------------------------------------------------------------------
+
+    /// Writes this snapshot's manifest and account payloads into `writer`.
+    ///
+    /// The manifest records the archive format version followed by each account's
+    /// [AccountHeader]; the per-account [AccountStorage] payloads are then concatenated in the
+    /// same order.
+    ///
+    /// # Errors
+    /// Returns [SnapshotError::TooManyAccounts] if this snapshot packages more than
+    /// [MAX_ACCOUNTS_PER_SNAPSHOT] accounts.
+    pub fn write_to<W: ByteWriter>(&self, writer: &mut W) -> Result<(), SnapshotError> {
+        if self.accounts.len() > MAX_ACCOUNTS_PER_SNAPSHOT {
+            return Err(SnapshotError::TooManyAccounts(self.accounts.len()));
+        }
+        // the above bound is well within u32::MAX, so this cast never truncates
+        let num_accounts = self.accounts.len() as u32;
+
+        writer.write_u8(SNAPSHOT_FORMAT_VERSION_1);
+        writer.write_u32(num_accounts);
+
+        for (header, _) in &self.accounts {
+            write_account_header(header, writer);
+        }
+        for (_, storage) in &self.accounts {
+            writer.write(storage);
+        }
+
+        Ok(())
+    }
+
+    /// Reads a snapshot previously written by [Snapshot::write_to] from `reader`.
+    ///
+    /// Each account's storage is deserialized and its commitment is validated against the
+    /// `storage_commitment` recorded for it in the manifest.
+    ///
+    /// # Errors
+    /// - Returns [SnapshotError::UnsupportedVersion] if the archive's format version is not
+    ///   understood by this build of the crate.
+    /// - Returns [SnapshotError::TooManyAccounts] if the manifest claims more than
+    ///   [MAX_ACCOUNTS_PER_SNAPSHOT] accounts. This is checked before any per-account allocation is
+    ///   made, so a corrupted or adversarial count can't be used to force an oversized allocation.
+    /// - Returns [SnapshotError::Deserialization] if the manifest or a storage payload is
+    ///   malformed.
+    /// - Returns [SnapshotError::StorageCommitmentMismatch] naming the first [AccountId] whose
+    ///   deserialized storage does not match its manifest commitment.
+    pub fn read_from<R: ByteReader>(reader: &mut R) -> Result<Self, SnapshotError> {
+        let version = reader.read_u8()?;
+        if version != SNAPSHOT_FORMAT_VERSION_1 {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let num_accounts = reader.read_u32()? as usize;
+        if num_accounts > MAX_ACCOUNTS_PER_SNAPSHOT {
+            return Err(SnapshotError::TooManyAccounts(num_accounts));
+        }
+
+        let headers = (0..num_accounts)
+            .map(|_| read_account_header(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut accounts = Vec::with_capacity(num_accounts);
+        for header in headers {
+            let storage = AccountStorage::read_from(reader)?;
+            if storage.commitment() != header.storage_commitment() {
+                return Err(SnapshotError::StorageCommitmentMismatch {
+                    account_id: header.id(),
+                    expected: header.storage_commitment(),
+                    actual: storage.commitment(),
+                });
+            }
+            accounts.push((header, storage));
+        }
+
+        Ok(Self { accounts })
+    }
+}
+
+/// Writes the `(id, nonce, vault_root, storage_commitment, code_commitment)` tuple that makes up
+/// an [AccountHeader] manifest entry.
+fn write_account_header<W: ByteWriter>(header: &AccountHeader, target: &mut W) {
+    target.write(header.id());
+    target.write(header.nonce());
+    target.write(header.vault_root());
+    target.write(header.storage_commitment());
+    target.write(header.code_commitment());
+}
+
+/// Reads the tuple written by [write_account_header] back into an [AccountHeader].
+fn read_account_header<R: ByteReader>(source: &mut R) -> Result<AccountHeader, DeserializationError> {
+    let id = source.read()?;
+    let nonce = source.read()?;
+    let vault_root = source.read()?;
+    let storage_commitment = source.read()?;
+    let code_commitment = source.read()?;
+
+    Ok(AccountHeader::new(id, nonce, vault_root, storage_commitment, code_commitment))
+}
+
+// SNAPSHOT ERROR
+// ================================================================================================
+
+/// Errors that can occur while writing or reading a [Snapshot].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    Deserialization(DeserializationError),
+    StorageCommitmentMismatch {
+        account_id: AccountId,
+        expected: Digest,
+        actual: Digest,
+    },
+    /// The snapshot packages, or its manifest claims, more than [MAX_ACCOUNTS_PER_SNAPSHOT]
+    /// accounts. The `usize` is that number of accounts.
+    TooManyAccounts(usize),
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Deserialization(err) => {
+                write!(f, "failed to deserialize snapshot: {err}")
+            },
+            SnapshotError::StorageCommitmentMismatch { account_id, expected, actual } => {
+                write!(
+                    f,
+                    "storage for account {account_id} does not match its manifest commitment: expected {expected}, got {actual}"
+                )
+            },
+            SnapshotError::TooManyAccounts(num_accounts) => {
+                write!(f, "snapshot packages {num_accounts} accounts, which exceeds the maximum of {MAX_ACCOUNTS_PER_SNAPSHOT}")
+            },
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "snapshot format version {version} is not supported")
+            },
+        }
+    }
+}
+
+impl From<DeserializationError> for SnapshotError {
+    fn from(err: DeserializationError) -> Self {
+        SnapshotError::Deserialization(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SnapshotError {}
+
+// TESTS
+// ================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use vm_core::Felt;
+
+    use vm_core::utils::ByteWriter;
+
+    use super::{
+        AccountHeader, AccountId, Digest, Snapshot, SnapshotError, MAX_ACCOUNTS_PER_SNAPSHOT,
+    };
+    use crate::accounts::AccountStorage;
+
+    fn mock_header_for(storage: &AccountStorage, id: AccountId) -> AccountHeader {
+        let commitment = storage.commitment();
+        AccountHeader::new(id, Felt::new(1), commitment, commitment, commitment)
+    }
+
+    #[test]
+    fn test_snapshot_write_to_read_from_roundtrip() {
+        let storage_a = AccountStorage::mock();
+        let storage_b = AccountStorage::mock();
+        let id_a = AccountId::try_from(Felt::new(1)).unwrap();
+        let id_b = AccountId::try_from(Felt::new(2)).unwrap();
+        let header_a = mock_header_for(&storage_a, id_a);
+        let header_b = mock_header_for(&storage_b, id_b);
+
+        let snapshot = Snapshot::new(vec![(header_a, storage_a), (header_b, storage_b)]);
+
+        let mut bytes = Vec::new();
+        snapshot.write_to(&mut bytes).unwrap();
+
+        let deserialized = Snapshot::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(snapshot, deserialized);
+    }
+
+    #[test]
+    fn test_snapshot_read_from_reports_mismatched_account_id() {
+        let good_storage = AccountStorage::mock();
+        let bad_storage = AccountStorage::mock();
+        let good_id = AccountId::try_from(Felt::new(1)).unwrap();
+        let bad_id = AccountId::try_from(Felt::new(2)).unwrap();
+        // both storages are the same mock, so the wrong commitment must come from elsewhere
+        let wrong_commitment = Digest::new([Felt::new(1), Felt::new(2), Felt::new(3), Felt::new(4)]);
+        let bad_storage_commitment = bad_storage.commitment();
+        let good_header = mock_header_for(&good_storage, good_id);
+        // deliberately give the second account a manifest commitment that does not match the
+        // storage that follows it in the archive
+        let bad_header =
+            AccountHeader::new(bad_id, Felt::new(1), wrong_commitment, wrong_commitment, wrong_commitment);
+
+        let snapshot = Snapshot::new(vec![(good_header, good_storage), (bad_header, bad_storage)]);
+
+        let mut bytes = Vec::new();
+        snapshot.write_to(&mut bytes).unwrap();
+
+        let err = Snapshot::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(
+            err,
+            SnapshotError::StorageCommitmentMismatch {
+                account_id: bad_id,
+                expected: wrong_commitment,
+                actual: bad_storage_commitment,
+            }
+        );
+    }
+
+    #[test]
+    fn test_snapshot_read_from_rejects_oversized_count_before_allocating() {
+        // a manifest claiming more accounts than MAX_ACCOUNTS_PER_SNAPSHOT must be rejected
+        // immediately, before the reader ever tries to size an allocation off of it -- so this
+        // must fail even though the archive has no account payloads to back up the claimed count
+        let mut bytes = Vec::new();
+        bytes.write_u8(super::SNAPSHOT_FORMAT_VERSION_1);
+        bytes.write_u32((MAX_ACCOUNTS_PER_SNAPSHOT + 1) as u32);
+
+        let err = Snapshot::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(err, SnapshotError::TooManyAccounts(MAX_ACCOUNTS_PER_SNAPSHOT + 1));
+    }
+}